@@ -2,73 +2,394 @@ use std::collections::HashMap;
 
 use anyhow::{Context, Result};
 use cranelift_codegen::entity::EntityRef;
+use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
 use cranelift_codegen::ir::types;
 use cranelift_codegen::ir::AbiParam;
 use cranelift_codegen::ir::Function;
 use cranelift_codegen::ir::InstBuilder;
+use cranelift_codegen::ir::MemFlags;
 use cranelift_codegen::ir::Signature;
+use cranelift_codegen::ir::SourceLoc;
 use cranelift_codegen::ir::UserFuncName;
-use cranelift_codegen::isa::CallConv;
+use cranelift_codegen::ir::Value;
+use cranelift_codegen::isa::{CallConv, OwnedTargetIsa};
 use cranelift_codegen::Context as CraneliftContext;
 use cranelift_frontend::Variable;
 use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
 use cranelift_jit::{JITBuilder, JITModule};
-use cranelift_module::{DataContext, Linkage, Module};
+use cranelift_module::{DataContext, DataId, FuncId, Linkage, Module};
+use cranelift_object::{ObjectBuilder, ObjectModule};
 use rnix::ast::BinOp;
 use rnix::ast::BinOpKind;
 use rnix::ast::Expr;
 use rnix::ast::HasEntry;
+use rnix::ast::Lambda;
 use rnix::ast::Literal;
 use rnix::ast::LiteralKind;
+use rnix::ast::Param;
+use rowan::ast::AstNode;
 
-/// Declare a single variable declaration.
+/// The Nix-level type a compiled `Value` carries. Threaded alongside every `Value` so that
+/// `compile_bin_op` can pick integer, float, or (rejecting) string instructions instead of
+/// assuming everything is an `i64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    I64,
+    F64,
+    Str,
+}
+
+/// A Cranelift `Value` tagged with the Nix type it represents.
+#[derive(Debug, Clone, Copy)]
+pub struct TypedValue {
+    pub value: Value,
+    pub kind: ValueKind,
+}
+
+impl TypedValue {
+    fn new(value: Value, kind: ValueKind) -> Self {
+        Self { value, kind }
+    }
+}
+
+/// The Cranelift type used to store a value of the given `ValueKind`.
+fn cranelift_type<M: Module>(module: &M, kind: ValueKind) -> types::Type {
+    match kind {
+        ValueKind::I64 => types::I64,
+        ValueKind::F64 => types::F64,
+        ValueKind::Str => module.target_config().pointer_type(),
+    }
+}
+
+/// Build a host `TargetIsa`. When `debug` is set, the `unwind_info` flag is turned on so that
+/// Cranelift computes unwind information for every compiled function. This crate does not turn
+/// that information into `.eh_frame` sections or register it with `JITModule`/`ObjectModule`.
+fn host_isa(debug: bool) -> Result<OwnedTargetIsa> {
+    let mut flag_builder = cranelift_codegen::settings::builder();
+    if debug {
+        flag_builder
+            .set("unwind_info", "true")
+            .context("failed to enable unwind_info")?;
+    }
+    let isa_builder =
+        cranelift_native::builder().map_err(|msg| anyhow::anyhow!("unsupported host: {msg}"))?;
+    Ok(isa_builder.finish(cranelift_codegen::settings::Flags::new(flag_builder))?)
+}
+
+/// Byte offset of the start of each line in `source`, in order, starting with line 1 at `0`.
+/// Used to turn the byte offsets recorded in `SourceLoc`s back into line/column pairs.
+fn line_starts(source: &str) -> Vec<u32> {
+    let mut starts = vec![0u32];
+    for (index, ch) in source.char_indices() {
+        if ch == '\n' {
+            starts.push((index + 1) as u32);
+        }
+    }
+    starts
+}
+
+/// Resolve a byte `offset` into a `(line, column)` pair, both 1-based, using the line table
+/// produced by `line_starts`.
+fn resolve_line_col(line_starts: &[u32], offset: u32) -> (u32, u32) {
+    match line_starts.binary_search(&offset) {
+        Ok(index) => (index as u32 + 1, 1),
+        Err(0) => (1, offset + 1),
+        Err(index) => {
+            let line_start = line_starts[index - 1];
+            (index as u32, offset - line_start + 1)
+        }
+    }
+}
+
+/// After a function has been defined, pull its resolved source-location table out of the
+/// codegen context: one `(code_offset, line, column)` row per range of machine code that
+/// carries a non-default `SourceLoc`, in code order. Mirrors how rustc_codegen_cranelift's debug context
+/// is populated from `CompiledCode::buffer`.
+fn collect_debug_rows(
+    codegen_context: &CraneliftContext,
+    line_starts: &[u32],
+) -> Vec<(u32, u32, u32)> {
+    let Some(compiled) = codegen_context.compiled_code() else {
+        return Vec::new();
+    };
+
+    compiled
+        .buffer
+        .get_srclocs_sorted()
+        .iter()
+        .filter(|row| !row.loc.is_default())
+        .map(|row| {
+            let (line, column) = resolve_line_col(line_starts, row.loc.bits());
+            (row.start, line, column)
+        })
+        .collect()
+}
+
+/// One lexical scope's worth of bindings: plain values alongside named lambdas, kept in
+/// separate maps since a `FuncId` doesn't carry a `Variable`/`ValueKind` to load like a value
+/// does.
+#[derive(Default)]
+struct Frame {
+    values: HashMap<String, (Variable, ValueKind)>,
+    functions: HashMap<String, FuncId>,
+}
+
+/// A lexically scoped stack of variable and named-lambda frames. Bindings are resolved from
+/// the innermost frame outward, so a `let` or lambda body can shadow names bound in an
+/// enclosing scope without clobbering them, and a binding declared in a nested scope is gone
+/// once that scope is popped.
+pub struct Env {
+    frames: Vec<Frame>,
+}
+
+impl Env {
+    pub fn new() -> Self {
+        Self {
+            frames: vec![Frame::default()],
+        }
+    }
+
+    /// Enter a new, empty scope.
+    pub fn push(&mut self) {
+        self.frames.push(Frame::default());
+    }
+
+    /// Leave the innermost scope.
+    pub fn pop(&mut self) {
+        self.frames.pop();
+    }
+
+    /// Whether `name` is already bound in the innermost scope.
+    pub fn contains_local(&self, name: &str) -> bool {
+        self.frames
+            .last()
+            .map(|frame| frame.values.contains_key(name))
+            .unwrap_or(false)
+    }
+
+    /// Bind `name` to a value in the innermost scope.
+    pub fn declare(&mut self, name: &str, var: Variable, kind: ValueKind) {
+        self.frames
+            .last_mut()
+            .expect("env always has at least one frame")
+            .values
+            .insert(name.into(), (var, kind));
+    }
+
+    /// Resolve `name` to a value, starting from the innermost scope and walking outward.
+    pub fn get(&self, name: &str) -> Option<(Variable, ValueKind)> {
+        self.frames
+            .iter()
+            .rev()
+            .find_map(|frame| frame.values.get(name).copied())
+    }
+
+    /// Bind `name` to a named lambda's `FuncId` in the innermost scope.
+    pub fn declare_function(&mut self, name: &str, func_id: FuncId) {
+        self.frames
+            .last_mut()
+            .expect("env always has at least one frame")
+            .functions
+            .insert(name.into(), func_id);
+    }
+
+    /// Resolve `name` to a named lambda's `FuncId`, starting from the innermost scope and
+    /// walking outward.
+    pub fn get_function(&self, name: &str) -> Option<FuncId> {
+        self.frames
+            .iter()
+            .rev()
+            .find_map(|frame| frame.functions.get(name).copied())
+    }
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Declare a single variable declaration in the innermost scope of `env`.
 pub fn declare_variable(
-    int: types::Type,
+    ty: types::Type,
     builder: &mut FunctionBuilder,
-    variables: &mut HashMap<String, Variable>,
+    env: &mut Env,
     index: &mut usize,
     name: &str,
+    kind: ValueKind,
 ) -> Variable {
     let var = Variable::new(*index);
-    if !variables.contains_key(name) {
-        variables.insert(name.into(), var);
-        builder.declare_var(var, int);
+    if !env.contains_local(name) {
+        env.declare(name, var, kind);
+        builder.declare_var(var, ty);
         *index += 1;
     }
     var
 }
 
-pub fn compile_literal(
-    builder: &mut FunctionBuilder,
-    literal: &Literal,
-) -> Result<cranelift_codegen::ir::Value> {
+pub fn compile_literal(builder: &mut FunctionBuilder, literal: &Literal) -> Result<TypedValue> {
     match literal.kind() {
         rnix::ast::LiteralKind::Integer(integer) => {
             let int_value = integer.value()?;
 
-            Ok(builder.ins().iconst(types::I64, int_value))
+            Ok(TypedValue::new(
+                builder.ins().iconst(types::I64, int_value),
+                ValueKind::I64,
+            ))
         }
         LiteralKind::Float(float) => {
             let float_value = float.value()?;
-            Ok(builder.ins().f64const(float_value))
+            Ok(TypedValue::new(
+                builder.ins().f64const(float_value),
+                ValueKind::F64,
+            ))
         }
         _ => Err(anyhow::anyhow!("unknown literal type {:?}", literal.kind())),
     }
 }
 
-pub fn compile_expression(
-    module: &mut JITModule,
+/// Declare and define a lambda as a real function in `module` under the symbol `name`, and
+/// return its `FuncId`. `name` should be a fresh module-level symbol generated by the caller,
+/// not the Nix-level binding name — reusing the Nix name would make shadowing or REPL
+/// rebinding try to redeclare and redefine the same symbol, which Cranelift rejects as a
+/// duplicate definition. Does not register the binding anywhere itself — it's the caller's job
+/// to remember the returned `FuncId` under whatever Nix name resolves to it (`Env`'s function
+/// frame for a lexically scoped `let` binding, or the top-level `functions` map for a
+/// REPL-persisted one), so that a scope going out of visibility is simply a matter of the
+/// caller dropping its own binding.
+///
+/// The body is compiled against a fresh `Env` holding only the parameter, with `functions` as
+/// the only other names in reach: lambdas do not close over the scope they're defined in, so a
+/// reference to an enclosing `let` binding or a sibling attribute that is not in `functions`
+/// fails with an unbound-variable error rather than resolving lexically.
+///
+/// When `debug` is set, every instruction is tagged with the source offset of the `Expr` that
+/// produced it (see `compile_expression`), and the resulting line-number rows are recorded in
+/// `debug_rows` under `name` once the function has been defined.
+#[allow(clippy::too_many_arguments)]
+fn compile_lambda_function<M: Module>(
+    module: &mut M,
+    data_context: &mut DataContext,
+    name: &str,
+    lambda: &Lambda,
+    functions: &mut HashMap<String, FuncId>,
+    lambda_counter: &mut usize,
+    debug: bool,
+    line_starts: &[u32],
+    debug_rows: &mut HashMap<String, Vec<(u32, u32, u32)>>,
+) -> Result<FuncId> {
+    let param = lambda.param().context("failed to get lambda param")?;
+    let param_name = match param {
+        Param::IdentParam(ident_param) => ident_param
+            .ident()
+            .context("failed to get lambda param ident")?
+            .to_string(),
+        Param::Pattern(_) => return Err(anyhow::anyhow!("pattern lambda params are unsupported")),
+    };
+    let body = lambda.body().context("failed to get lambda body")?;
+
+    let mut signature = Signature::new(CallConv::triple_default(module.isa().triple()));
+    signature.params.push(AbiParam::new(types::I64));
+    signature.returns.push(AbiParam::new(types::I64));
+
+    let func_id = module
+        .declare_function(name, Linkage::Local, &signature)
+        .context("failed to declare lambda function")?;
+
+    let mut func_ctx = FunctionBuilderContext::new();
+    let mut func = Function::with_name_signature(UserFuncName::user(0, func_id.as_u32()), signature);
+
+    {
+        let mut func_builder = FunctionBuilder::new(&mut func, &mut func_ctx);
+
+        let entry_block = func_builder.create_block();
+        func_builder.append_block_params_for_function_params(entry_block);
+        func_builder.switch_to_block(entry_block);
+        func_builder.seal_block(entry_block);
+
+        let mut param_env = Env::new();
+        let mut param_variable_index = 0;
+        let param_var = declare_variable(
+            types::I64,
+            &mut func_builder,
+            &mut param_env,
+            &mut param_variable_index,
+            &param_name,
+            ValueKind::I64,
+        );
+        let param_value = func_builder.block_params(entry_block)[0];
+        func_builder.def_var(param_var, param_value);
+
+        let body_value = compile_expression(
+            module,
+            data_context,
+            &mut func_builder,
+            &body,
+            &mut param_variable_index,
+            &mut param_env,
+            functions,
+            lambda_counter,
+            debug,
+            line_starts,
+            debug_rows,
+        )?
+        .context("failed to compile lambda body")?;
+
+        if body_value.kind != ValueKind::I64 {
+            return Err(anyhow::anyhow!(
+                "lambda bodies must currently evaluate to an integer, got {:?}",
+                body_value.kind
+            ));
+        }
+
+        func_builder.ins().return_(&[body_value.value]);
+        func_builder.finalize();
+    }
+
+    let mut codegen_context = module.make_context();
+    codegen_context.func = func;
+    module
+        .define_function(func_id, &mut codegen_context)
+        .context("failed to define lambda function")?;
+
+    if debug {
+        let rows = collect_debug_rows(&codegen_context, line_starts);
+        debug_rows.insert(name.to_string(), rows);
+    }
+
+    module.clear_context(&mut codegen_context);
+
+    Ok(func_id)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn compile_expression<M: Module>(
+    module: &mut M,
     data_context: &mut DataContext,
     builder: &mut FunctionBuilder,
     expr: &Expr,
     variable_index: &mut usize,
-    variables: &mut HashMap<String, Variable>,
-) -> Result<Option<cranelift_codegen::ir::Value>> {
-    let compile_bin_op = |module: &mut JITModule,
+    env: &mut Env,
+    functions: &mut HashMap<String, FuncId>,
+    lambda_counter: &mut usize,
+    debug: bool,
+    line_starts: &[u32],
+    debug_rows: &mut HashMap<String, Vec<(u32, u32, u32)>>,
+) -> Result<Option<TypedValue>> {
+    if debug {
+        let offset: u32 = expr.syntax().text_range().start().into();
+        builder.set_srcloc(SourceLoc::new(offset));
+    }
+
+    let compile_bin_op = |module: &mut M,
                           data_context: &mut DataContext,
                           builder: &mut FunctionBuilder,
                           variable_index: &mut usize,
-                          variables: &mut HashMap<String, Variable>,
+                          env: &mut Env,
+                          functions: &mut HashMap<String, FuncId>,
+                          lambda_counter: &mut usize,
+                          line_starts: &[u32],
+                          debug_rows: &mut HashMap<String, Vec<(u32, u32, u32)>>,
                           operator: &BinOp| {
         let left = compile_expression(
             module,
@@ -78,7 +399,12 @@ pub fn compile_expression(
                 .lhs()
                 .context("failed to compile left expression")?,
             variable_index,
-            variables,
+            env,
+            functions,
+            lambda_counter,
+            debug,
+            line_starts,
+            debug_rows,
         )?;
         let right = compile_expression(
             module,
@@ -88,7 +414,12 @@ pub fn compile_expression(
                 .rhs()
                 .context("failed to compile right expression")?,
             variable_index,
-            variables,
+            env,
+            functions,
+            lambda_counter,
+            debug,
+            line_starts,
+            debug_rows,
         )?;
 
         if left.is_none() || right.is_none() {
@@ -98,43 +429,134 @@ pub fn compile_expression(
         let left = left.unwrap();
         let right = right.unwrap();
 
+        let (left, right, result_kind) = match (left.kind, right.kind) {
+            (ValueKind::I64, ValueKind::I64) => (left.value, right.value, ValueKind::I64),
+            (ValueKind::F64, ValueKind::F64) => (left.value, right.value, ValueKind::F64),
+            (ValueKind::I64, ValueKind::F64) => {
+                let promoted = builder.ins().fcvt_from_sint(types::F64, left.value);
+                (promoted, right.value, ValueKind::F64)
+            }
+            (ValueKind::F64, ValueKind::I64) => {
+                let promoted = builder.ins().fcvt_from_sint(types::F64, right.value);
+                (left.value, promoted, ValueKind::F64)
+            }
+            (lk, rk) => {
+                return Err(anyhow::anyhow!(
+                    "cannot apply operator to {:?} and {:?} operands",
+                    lk,
+                    rk
+                ))
+            }
+        };
+        let is_float = result_kind == ValueKind::F64;
+
         match operator.operator().context("failed to get operator")? {
-            BinOpKind::Add => Ok(Some(builder.ins().iadd(left, right))),
-            BinOpKind::Sub => Ok(Some(builder.ins().isub(left, right))),
-            BinOpKind::Mul => Ok(Some(builder.ins().imul(left, right))),
-            BinOpKind::Div => Ok(Some(builder.ins().udiv(left, right))),
-            BinOpKind::Less => Ok(Some(builder.ins().icmp(
-                cranelift_codegen::ir::condcodes::IntCC::SignedLessThan,
-                left,
-                right,
-            ))),
-            BinOpKind::LessOrEq => Ok(Some(builder.ins().icmp(
-                cranelift_codegen::ir::condcodes::IntCC::SignedLessThanOrEqual,
-                left,
-                right,
-            ))),
-            BinOpKind::More => Ok(Some(builder.ins().icmp(
-                cranelift_codegen::ir::condcodes::IntCC::SignedGreaterThan,
-                left,
-                right,
-            ))),
-            BinOpKind::MoreOrEq => Ok(Some(builder.ins().icmp(
-                cranelift_codegen::ir::condcodes::IntCC::SignedGreaterThanOrEqual,
-                left,
-                right,
-            ))),
-            BinOpKind::Equal => Ok(Some(builder.ins().icmp(
-                cranelift_codegen::ir::condcodes::IntCC::Equal,
-                left,
-                right,
-            ))),
-            BinOpKind::NotEqual => Ok(Some(builder.ins().icmp(
-                cranelift_codegen::ir::condcodes::IntCC::NotEqual,
-                left,
-                right,
-            ))),
-            BinOpKind::And => Ok(Some(builder.ins().band(left, right))),
-            BinOpKind::Or => Ok(Some(builder.ins().bor(left, right))),
+            BinOpKind::Add => {
+                let value = if is_float {
+                    builder.ins().fadd(left, right)
+                } else {
+                    builder.ins().iadd(left, right)
+                };
+                Ok(Some(TypedValue::new(value, result_kind)))
+            }
+            BinOpKind::Sub => {
+                let value = if is_float {
+                    builder.ins().fsub(left, right)
+                } else {
+                    builder.ins().isub(left, right)
+                };
+                Ok(Some(TypedValue::new(value, result_kind)))
+            }
+            BinOpKind::Mul => {
+                let value = if is_float {
+                    builder.ins().fmul(left, right)
+                } else {
+                    builder.ins().imul(left, right)
+                };
+                Ok(Some(TypedValue::new(value, result_kind)))
+            }
+            BinOpKind::Div => {
+                let value = if is_float {
+                    builder.ins().fdiv(left, right)
+                } else {
+                    builder.ins().udiv(left, right)
+                };
+                Ok(Some(TypedValue::new(value, result_kind)))
+            }
+            BinOpKind::Less => {
+                let value = if is_float {
+                    builder.ins().fcmp(FloatCC::LessThan, left, right)
+                } else {
+                    builder.ins().icmp(IntCC::SignedLessThan, left, right)
+                };
+                let value = builder.ins().uextend(types::I64, value);
+                Ok(Some(TypedValue::new(value, ValueKind::I64)))
+            }
+            BinOpKind::LessOrEq => {
+                let value = if is_float {
+                    builder.ins().fcmp(FloatCC::LessThanOrEqual, left, right)
+                } else {
+                    builder.ins().icmp(IntCC::SignedLessThanOrEqual, left, right)
+                };
+                let value = builder.ins().uextend(types::I64, value);
+                Ok(Some(TypedValue::new(value, ValueKind::I64)))
+            }
+            BinOpKind::More => {
+                let value = if is_float {
+                    builder.ins().fcmp(FloatCC::GreaterThan, left, right)
+                } else {
+                    builder.ins().icmp(IntCC::SignedGreaterThan, left, right)
+                };
+                let value = builder.ins().uextend(types::I64, value);
+                Ok(Some(TypedValue::new(value, ValueKind::I64)))
+            }
+            BinOpKind::MoreOrEq => {
+                let value = if is_float {
+                    builder.ins().fcmp(FloatCC::GreaterThanOrEqual, left, right)
+                } else {
+                    builder
+                        .ins()
+                        .icmp(IntCC::SignedGreaterThanOrEqual, left, right)
+                };
+                let value = builder.ins().uextend(types::I64, value);
+                Ok(Some(TypedValue::new(value, ValueKind::I64)))
+            }
+            BinOpKind::Equal => {
+                let value = if is_float {
+                    builder.ins().fcmp(FloatCC::Equal, left, right)
+                } else {
+                    builder.ins().icmp(IntCC::Equal, left, right)
+                };
+                let value = builder.ins().uextend(types::I64, value);
+                Ok(Some(TypedValue::new(value, ValueKind::I64)))
+            }
+            BinOpKind::NotEqual => {
+                let value = if is_float {
+                    builder.ins().fcmp(FloatCC::NotEqual, left, right)
+                } else {
+                    builder.ins().icmp(IntCC::NotEqual, left, right)
+                };
+                let value = builder.ins().uextend(types::I64, value);
+                Ok(Some(TypedValue::new(value, ValueKind::I64)))
+            }
+            BinOpKind::And => {
+                if is_float {
+                    return Err(anyhow::anyhow!("cannot apply `&&` to float operands"));
+                }
+                Ok(Some(TypedValue::new(
+                    builder.ins().band(left, right),
+                    ValueKind::I64,
+                )))
+            }
+            BinOpKind::Or => {
+                if is_float {
+                    return Err(anyhow::anyhow!("cannot apply `||` to float operands"));
+                }
+                Ok(Some(TypedValue::new(
+                    builder.ins().bor(left, right),
+                    ValueKind::I64,
+                )))
+            }
 
             // TODO: Implement the rest of the operators
             _ => Err(anyhow::anyhow!(
@@ -146,107 +568,301 @@ pub fn compile_expression(
 
     match expr {
         Expr::Lambda(lambda) => {
-            let _param = lambda.param().context("failed to get lambda param")?;
-            let body = lambda.body().context("failed to get lambda body")?;
-            let func_name = format!(
-                "lambda_{}",
-                std::time::SystemTime::now().elapsed().unwrap().as_nanos()
-            );
+            let name = format!("lambda_{}", *lambda_counter);
+            *lambda_counter += 1;
 
-            // Create a new function with the compiled body and the parameter
-            let mut func_ctx = FunctionBuilderContext::new();
-            let mut func = Function::with_name_signature(
-                UserFuncName::testcase(func_name),
-                Signature::new(CallConv::triple_default(module.isa().triple())),
-            );
-            func.signature.params.push(AbiParam::new(types::I64));
-            func.signature.returns.push(AbiParam::new(types::I64));
-
-            let mut func_builder = FunctionBuilder::new(&mut func, &mut func_ctx);
+            let func_id = compile_lambda_function(
+                module,
+                data_context,
+                &name,
+                lambda,
+                functions,
+                lambda_counter,
+                debug,
+                line_starts,
+                debug_rows,
+            )?;
 
-            let entry_block = func_builder.create_block();
-            func_builder.append_block_params_for_function_params(entry_block);
-            func_builder.switch_to_block(entry_block);
-            func_builder.seal_block(entry_block);
+            let func_ref = module.declare_func_in_func(func_id, builder.func);
+            let pointer = module.target_config().pointer_type();
+            Ok(Some(TypedValue::new(
+                builder.ins().func_addr(pointer, func_ref),
+                ValueKind::I64,
+            )))
+        }
+        Expr::Apply(apply) => {
+            let callee = apply.lambda().context("failed to get apply callee")?;
+            let argument = apply.argument().context("failed to get apply argument")?;
 
-            let body_value = compile_expression(
+            let arg_value = compile_expression(
                 module,
                 data_context,
-                &mut func_builder,
-                &body,
+                builder,
+                &argument,
                 variable_index,
-                variables,
-            )?;
-            if body_value.is_none() {
-                return Err(anyhow::anyhow!("failed to compile lambda body"));
+                env,
+                functions,
+                lambda_counter,
+                debug,
+                line_starts,
+                debug_rows,
+            )?
+            .context("failed to compile call argument")?;
+
+            if arg_value.kind != ValueKind::I64 {
+                return Err(anyhow::anyhow!(
+                    "only integer arguments are currently supported in function calls"
+                ));
             }
 
-            func_builder.ins().return_(&[body_value.unwrap()]);
+            if let Expr::Ident(ident) = &callee {
+                let name = ident.to_string();
+                let func_id = env.get_function(&name).or_else(|| functions.get(&name).copied());
+                if let Some(func_id) = func_id {
+                    let func_ref = module.declare_func_in_func(func_id, builder.func);
+                    let call = builder.ins().call(func_ref, &[arg_value.value]);
+                    return Ok(Some(TypedValue::new(
+                        builder.inst_results(call)[0],
+                        ValueKind::I64,
+                    )));
+                }
+            }
 
-            func_builder.finalize();
+            let callee_value = compile_expression(
+                module,
+                data_context,
+                builder,
+                &callee,
+                variable_index,
+                env,
+                functions,
+                lambda_counter,
+                debug,
+                line_starts,
+                debug_rows,
+            )?
+            .context("failed to compile call target")?;
 
-            Ok(None)
+            let mut signature = module.make_signature();
+            signature.params.push(AbiParam::new(types::I64));
+            signature.returns.push(AbiParam::new(types::I64));
+            let sig_ref = builder.import_signature(signature);
+            let call = builder
+                .ins()
+                .call_indirect(sig_ref, callee_value.value, &[arg_value.value]);
+            Ok(Some(TypedValue::new(
+                builder.inst_results(call)[0],
+                ValueKind::I64,
+            )))
         }
         Expr::Ident(ident) => {
             let ident = ident.to_string();
-            let variable = variables.get(&ident).context("failed to get variable")?;
-            Ok(Some(builder.use_var(*variable)))
+            if let Some((variable, kind)) = env.get(&ident) {
+                return Ok(Some(TypedValue::new(builder.use_var(variable), kind)));
+            }
+
+            // Not a plain value: a named lambda referenced on its own (rather than applied)
+            // resolves the same way an anonymous `Expr::Lambda` does, as a function address.
+            let func_id = env
+                .get_function(&ident)
+                .or_else(|| functions.get(&ident).copied())
+                .with_context(|| {
+                    format!(
+                        "variable `{ident}` is unbound here (note: lambda bodies do not capture \
+                         bindings from their enclosing scope, so names from an outer `let` or a \
+                         sibling attribute are not visible inside a lambda)"
+                    )
+                })?;
+            let func_ref = module.declare_func_in_func(func_id, builder.func);
+            let pointer = module.target_config().pointer_type();
+            Ok(Some(TypedValue::new(
+                builder.ins().func_addr(pointer, func_ref),
+                ValueKind::I64,
+            )))
         }
         Expr::LetIn(let_in) => {
             let body = let_in.body().context("failed to get let in body")?;
             let values = let_in.attrpath_values();
 
-            for value in values {
-                let attr_path = value.attrpath().context("failed to get attr path")?;
-                let key = attr_path
-                    .attrs()
-                    .map(|attr| match attr {
-                        rnix::ast::Attr::Ident(ident) => ident.to_string(),
-                        rnix::ast::Attr::Str(str) => str.to_string(),
-                        _ => "".to_string(),
-                    })
-                    .collect::<Vec<String>>()
-                    .join(".");
-                let value = value.value().context("failed to get value")?;
-                let value = compile_expression(
+            env.push();
+
+            let result = (|| -> Result<Option<TypedValue>> {
+                for value in values {
+                    let attr_path = value.attrpath().context("failed to get attr path")?;
+                    let key = attr_path
+                        .attrs()
+                        .map(|attr| match attr {
+                            rnix::ast::Attr::Ident(ident) => ident.to_string(),
+                            rnix::ast::Attr::Str(str) => str.to_string(),
+                            _ => "".to_string(),
+                        })
+                        .collect::<Vec<String>>()
+                        .join(".");
+                    let value_expr = value.value().context("failed to get value")?;
+
+                    if let Expr::Lambda(lambda) = &value_expr {
+                        // Compile under a fresh module-level symbol, like the REPL's
+                        // `repl_bind_N` path does, so shadowing this binding in a sibling or
+                        // nested `let` never collides with a still-live previous definition of
+                        // the same symbol. The Nix name is then bound in `env`'s innermost
+                        // scope, not the flat `functions` map, so it is only visible for the
+                        // lifetime of this `let`'s scope.
+                        let symbol_name = format!("let_lambda_{}", *lambda_counter);
+                        *lambda_counter += 1;
+                        let func_id = compile_lambda_function(
+                            module,
+                            data_context,
+                            &symbol_name,
+                            lambda,
+                            functions,
+                            lambda_counter,
+                            debug,
+                            line_starts,
+                            debug_rows,
+                        )?;
+                        env.declare_function(&key, func_id);
+                        continue;
+                    }
+
+                    let value = compile_expression(
+                        module,
+                        data_context,
+                        builder,
+                        &value_expr,
+                        variable_index,
+                        env,
+                        functions,
+                        lambda_counter,
+                        debug,
+                        line_starts,
+                        debug_rows,
+                    )?;
+
+                    let value = value.context("failed to compile let in value")?;
+
+                    let variable = declare_variable(
+                        cranelift_type(module, value.kind),
+                        builder,
+                        env,
+                        variable_index,
+                        &key,
+                        value.kind,
+                    );
+
+                    builder.def_var(variable, value.value);
+                }
+
+                compile_expression(
                     module,
                     data_context,
                     builder,
-                    &value,
+                    &body,
                     variable_index,
-                    variables,
-                )?;
+                    env,
+                    functions,
+                    lambda_counter,
+                    debug,
+                    line_starts,
+                    debug_rows,
+                )
+            })();
 
-                if value.is_none() {
-                    return Err(anyhow::anyhow!("failed to compile let in value"));
-                }
+            env.pop();
 
-                let variable = declare_variable(
-                    module.isa().pointer_type().as_int(),
-                    builder,
-                    variables,
-                    variable_index,
-                    &key,
-                );
+            result
+        }
+        Expr::IfElse(if_else) => {
+            let condition = if_else.condition().context("failed to get if condition")?;
+            let then_body = if_else.body().context("failed to get if body")?;
+            let else_body = if_else
+                .else_body()
+                .context("failed to get if else body")?;
 
-                builder.def_var(variable, value.unwrap());
-            }
+            let cond_value = compile_expression(
+                module,
+                data_context,
+                builder,
+                &condition,
+                variable_index,
+                env,
+                functions,
+                lambda_counter,
+                debug,
+                line_starts,
+                debug_rows,
+            )?
+            .context("failed to compile if condition")?;
 
-            compile_expression(
+            let then_block = builder.create_block();
+            let else_block = builder.create_block();
+            let merge_block = builder.create_block();
+
+            builder
+                .ins()
+                .brif(cond_value.value, then_block, &[], else_block, &[]);
+
+            builder.switch_to_block(then_block);
+            builder.seal_block(then_block);
+            let then_value = compile_expression(
                 module,
                 data_context,
                 builder,
-                &body,
+                &then_body,
                 variable_index,
-                variables,
-            )
+                env,
+                functions,
+                lambda_counter,
+                debug,
+                line_starts,
+                debug_rows,
+            )?
+            .context("if branch must produce a value")?;
+            builder.append_block_param(merge_block, cranelift_type(module, then_value.kind));
+            builder.ins().jump(merge_block, &[then_value.value]);
+
+            builder.switch_to_block(else_block);
+            builder.seal_block(else_block);
+            let else_value = compile_expression(
+                module,
+                data_context,
+                builder,
+                &else_body,
+                variable_index,
+                env,
+                functions,
+                lambda_counter,
+                debug,
+                line_starts,
+                debug_rows,
+            )?
+            .context("else branch must produce a value")?;
+            if else_value.kind != then_value.kind {
+                return Err(anyhow::anyhow!(
+                    "if/else branches must produce the same type, got {:?} and {:?}",
+                    then_value.kind,
+                    else_value.kind
+                ));
+            }
+            builder.ins().jump(merge_block, &[else_value.value]);
+
+            builder.seal_block(merge_block);
+            builder.switch_to_block(merge_block);
+
+            Ok(Some(TypedValue::new(
+                builder.block_params(merge_block)[0],
+                then_value.kind,
+            )))
         }
         Expr::BinOp(operator) => compile_bin_op(
             module,
             data_context,
             builder,
             variable_index,
-            variables,
+            env,
+            functions,
+            lambda_counter,
+            line_starts,
+            debug_rows,
             operator,
         ),
         Expr::Literal(node) => Ok(Some(compile_literal(builder, node)?)),
@@ -266,7 +882,12 @@ pub fn compile_expression(
                             builder,
                             &expr.context("failed to compile interpolation expression")?,
                             variable_index,
-                            variables,
+                            env,
+                            functions,
+                            lambda_counter,
+                            debug,
+                            line_starts,
+                            debug_rows,
                         )?;
 
                         return Ok(value);
@@ -287,40 +908,114 @@ pub fn compile_expression(
             let local_id = module.declare_data_in_func(data_id, builder.func);
 
             let pointer = module.target_config().pointer_type();
-            Ok(Some(builder.ins().symbol_value(pointer, local_id)))
+            Ok(Some(TypedValue::new(
+                builder.ins().symbol_value(pointer, local_id),
+                ValueKind::Str,
+            )))
         }
         _ => Err(anyhow::anyhow!("unknown expression {:?}", expr)),
     }
 }
 
-pub struct Compiler {
-    module: JITModule,
+/// Append a best-effort `.debug_line` section covering every row in `debug_rows` to `object`.
+/// Modeled on rustc_codegen_cranelift's `debuginfo` module: a single line-number program for
+/// the input file, with one sequence per compiled function. No `.debug_info` compile unit is
+/// written. Addresses are
+/// recorded relative to each function's start, since the final load address of a function is
+/// only known to the linker; a fuller implementation would emit per-row relocations against the
+/// function's symbol instead.
+fn write_debug_line_section(
+    source_path: &str,
+    debug_rows: &HashMap<String, Vec<(u32, u32, u32)>>,
+) -> Result<Vec<u8>> {
+    let encoding = gimli::Encoding {
+        format: gimli::Format::Dwarf32,
+        version: 4,
+        address_size: 8,
+    };
+
+    let (comp_dir, file_name) = match source_path.rsplit_once('/') {
+        Some((dir, file)) => (dir.to_string(), file.to_string()),
+        None => (".".to_string(), source_path.to_string()),
+    };
+
+    let mut line_program = gimli::write::LineProgram::new(
+        encoding,
+        gimli::LineEncoding::default(),
+        gimli::write::LineString::String(comp_dir.into_bytes()),
+        gimli::write::LineString::String(file_name.clone().into_bytes()),
+        None,
+    );
+    let file_id = line_program.add_file(
+        gimli::write::LineString::String(file_name.into_bytes()),
+        line_program.default_directory(),
+        None,
+    );
+
+    for rows in debug_rows.values() {
+        if rows.is_empty() {
+            continue;
+        }
+
+        line_program.begin_sequence(None);
+        for (offset, line, column) in rows {
+            {
+                let row = line_program.row();
+                row.address_offset = u64::from(*offset);
+                row.file = file_id;
+                row.line = u64::from(*line);
+                row.column = u64::from(*column);
+            }
+            line_program.generate_row();
+        }
+        let end_offset = rows.last().map(|(offset, _, _)| u64::from(*offset) + 1).unwrap_or(1);
+        line_program.end_sequence(end_offset);
+    }
+
+    let mut sections = gimli::write::Sections::new(gimli::write::EndianVec::new(
+        gimli::RunTimeEndian::Little,
+    ));
+    line_program
+        .write(
+            &mut sections.debug_line,
+            encoding,
+            &gimli::write::DebugLineStrOffsets::none(),
+            &gimli::write::DebugStrOffsets::none(),
+        )
+        .context("failed to write debug_line section")?;
+
+    Ok(sections.debug_line.slice().to_vec())
+}
+
+/// Compiles Nix expressions using the given Cranelift `Module` backend. The same
+/// `compile_expression` lowering serves both `JITModule` (for immediate execution) and
+/// `ObjectModule` (for emitting a relocatable object file), since all module operations are
+/// routed through the `cranelift_module::Module` trait.
+pub struct Compiler<M: Module> {
+    module: M,
     function_context: FunctionBuilderContext,
     codegen_context: CraneliftContext,
     data_context: DataContext,
     variable_index: usize,
-    variables: HashMap<String, Variable>,
+    functions: HashMap<String, FuncId>,
+    lambda_counter: usize,
+    /// Top-level bindings persisted as module-level data so that a REPL session can read
+    /// them back in later, separately compiled inputs. Empty outside of REPL use.
+    globals: HashMap<String, (DataId, ValueKind)>,
+    repl_counter: usize,
+    /// Whether to tag instructions with source locations and emit DWARF line info.
+    debug: bool,
+    /// Resolved `(code_offset, line, column)` rows recorded for every function compiled with
+    /// `debug` set, keyed by function name.
+    debug_rows: HashMap<String, Vec<(u32, u32, u32)>>,
 }
 
-impl Compiler {
-    pub fn new() -> Result<Self> {
-        let builder = JITBuilder::new(cranelift_module::default_libcall_names())?;
-        let module = JITModule::new(builder);
-        let function_context = FunctionBuilderContext::new();
-        let codegen_context = module.make_context();
-        let data_context = DataContext::new();
-
-        Ok(Self {
-            module,
-            function_context,
-            codegen_context,
-            data_context,
-            variable_index: 0,
-            variables: HashMap::new(),
-        })
-    }
-
-    pub fn compile(&mut self, expr: &Expr) -> Result<()> {
+impl<M: Module> Compiler<M> {
+    /// Compile `expr` into a fresh function named `name`, seeding a new `Env` with every
+    /// persisted global so `Ident` resolution finds them like ordinary variables. `source` is
+    /// the original text `expr` was parsed from, used to resolve debug line/column info when
+    /// `self.debug` is set. Returns the function's id and the Nix type of its result.
+    fn compile_named(&mut self, expr: &Expr, name: &str, source: &str) -> Result<(FuncId, ValueKind)> {
         self.codegen_context.func.signature.params = vec![];
         self.codegen_context.func.signature.returns = vec![AbiParam::new(types::I64)];
 
@@ -330,35 +1025,340 @@ impl Compiler {
         builder.switch_to_block(entry_block);
         builder.seal_block(entry_block);
 
+        let mut env = Env::new();
+        let pointer_type = self.module.target_config().pointer_type();
+        for (global_name, (data_id, kind)) in self.globals.iter() {
+            let local_id = self.module.declare_data_in_func(*data_id, builder.func);
+            let addr = builder.ins().symbol_value(pointer_type, local_id);
+            let ty = cranelift_type(&self.module, *kind);
+            let loaded = builder.ins().load(ty, MemFlags::trusted(), addr, 0);
+            let var = declare_variable(
+                ty,
+                &mut builder,
+                &mut env,
+                &mut self.variable_index,
+                global_name,
+                *kind,
+            );
+            builder.def_var(var, loaded);
+        }
+
         let mut stack = vec![];
+        let source_line_starts = line_starts(source);
         let value = compile_expression(
             &mut self.module,
             &mut self.data_context,
             &mut builder,
             expr,
             &mut self.variable_index,
-            &mut self.variables,
+            &mut env,
+            &mut self.functions,
+            &mut self.lambda_counter,
+            self.debug,
+            &source_line_starts,
+            &mut self.debug_rows,
         )?;
         if let Some(value) = value {
             stack.push(value);
         }
 
         let return_value = stack.pop().context("failed to get return value")?;
+        let return_type = cranelift_type(&self.module, return_value.kind);
+        builder.func.signature.returns = vec![AbiParam::new(return_type)];
 
-        builder.ins().return_(&[return_value]);
+        builder.ins().return_(&[return_value.value]);
         builder.finalize();
-        let id = self.module.declare_function(
-            "main",
-            Linkage::Export,
-            &self.codegen_context.func.signature,
-        )?;
+
+        let id =
+            self.module
+                .declare_function(name, Linkage::Export, &self.codegen_context.func.signature)?;
         self.module.define_function(id, &mut self.codegen_context)?;
+
+        if self.debug {
+            let rows = collect_debug_rows(&self.codegen_context, &source_line_starts);
+            self.debug_rows.insert(name.to_string(), rows);
+        }
+
         self.module.clear_context(&mut self.codegen_context);
+
+        Ok((id, return_value.kind))
+    }
+
+    /// The resolved debug line rows gathered so far, keyed by compiled function name. Empty
+    /// unless the compiler was constructed with `debug: true`.
+    pub fn debug_rows(&self) -> &HashMap<String, Vec<(u32, u32, u32)>> {
+        &self.debug_rows
+    }
+}
+
+impl Compiler<JITModule> {
+    /// Build a JIT compiler. When `debug` is set, compiled functions carry source locations and
+    /// the ISA is built with `unwind_info` enabled; the resulting unwind info is not registered
+    /// with the JIT module.
+    pub fn new(debug: bool) -> Result<Self> {
+        let isa = host_isa(debug)?;
+        let builder = JITBuilder::with_isa(isa, cranelift_module::default_libcall_names());
+        let module = JITModule::new(builder);
+        let function_context = FunctionBuilderContext::new();
+        let codegen_context = module.make_context();
+        let data_context = DataContext::new();
+
+        Ok(Self {
+            module,
+            function_context,
+            codegen_context,
+            data_context,
+            variable_index: 0,
+            functions: HashMap::new(),
+            lambda_counter: 0,
+            globals: HashMap::new(),
+            repl_counter: 0,
+            debug,
+            debug_rows: HashMap::new(),
+        })
+    }
+
+    /// JIT-compile `expr` (parsed from `source`) and immediately run it, printing the result.
+    pub fn compile(&mut self, expr: &Expr, source: &str) -> Result<()> {
+        self.run_and_print(expr, source)
+    }
+
+    /// Evaluate a single REPL input, parsed from `source`. A top-level `let ... in` binds its
+    /// attributes as persisted globals (functions are registered directly, plain values are
+    /// stored to module data) before printing the result of its body; any other expression is
+    /// just run and printed. This keeps bindings entered on one line visible on the next.
+    pub fn eval(&mut self, expr: &Expr, source: &str) -> Result<()> {
+        match expr {
+            Expr::LetIn(let_in) => {
+                for value in let_in.attrpath_values() {
+                    let attr_path = value.attrpath().context("failed to get attr path")?;
+                    let key = attr_path
+                        .attrs()
+                        .map(|attr| match attr {
+                            rnix::ast::Attr::Ident(ident) => ident.to_string(),
+                            rnix::ast::Attr::Str(str) => str.to_string(),
+                            _ => "".to_string(),
+                        })
+                        .collect::<Vec<String>>()
+                        .join(".");
+                    let value_expr = value.value().context("failed to get value")?;
+
+                    if let Expr::Lambda(lambda) = &value_expr {
+                        // Compile under a fresh module-level name every time, like
+                        // `bind_global` does for plain values, so redefining `key` in a later
+                        // REPL line doesn't collide with the still-live previous definition;
+                        // `functions` is then remapped so calls to `key` see the new one.
+                        self.repl_counter += 1;
+                        let fn_name = format!("repl_bind_{}", self.repl_counter);
+                        let func_id = compile_lambda_function(
+                            &mut self.module,
+                            &mut self.data_context,
+                            &fn_name,
+                            lambda,
+                            &mut self.functions,
+                            &mut self.lambda_counter,
+                            self.debug,
+                            &line_starts(source),
+                            &mut self.debug_rows,
+                        )?;
+                        self.functions.insert(key, func_id);
+                        continue;
+                    }
+
+                    self.bind_global(&key, &value_expr, source)?;
+                }
+
+                let body = let_in.body().context("failed to get let in body")?;
+                self.run_and_print(&body, source)
+            }
+            _ => self.run_and_print(expr, source),
+        }
+    }
+
+    /// Run `expr` (parsed from `source`) as a one-off function and print its result.
+    fn run_and_print(&mut self, expr: &Expr, source: &str) -> Result<()> {
+        self.repl_counter += 1;
+        let name = format!("repl_{}", self.repl_counter);
+
+        let (id, kind) = self.compile_named(expr, &name, source)?;
         self.module.finalize_definitions()?;
         let code_ptr = self.module.get_finalized_function(id);
-        let main: fn() -> i64 = unsafe { std::mem::transmute(code_ptr) };
-        println!("{}", main());
+
+        match kind {
+            ValueKind::F64 => {
+                let main: fn() -> f64 = unsafe { std::mem::transmute(code_ptr) };
+                println!("{}", main());
+            }
+            ValueKind::I64 | ValueKind::Str => {
+                let main: fn() -> i64 = unsafe { std::mem::transmute(code_ptr) };
+                println!("{}", main());
+            }
+        }
 
         Ok(())
     }
+
+    /// Evaluate `value_expr` (parsed from `source`) once and persist its result as module data
+    /// under `name`, so later calls to `compile_named` can load it back into scope.
+    fn bind_global(&mut self, name: &str, value_expr: &Expr, source: &str) -> Result<()> {
+        self.repl_counter += 1;
+        let fn_name = format!("repl_bind_{}", self.repl_counter);
+
+        let (id, kind) = self.compile_named(value_expr, &fn_name, source)?;
+        self.module.finalize_definitions()?;
+        let code_ptr = self.module.get_finalized_function(id);
+
+        let bytes = match kind {
+            ValueKind::F64 => {
+                let f: fn() -> f64 = unsafe { std::mem::transmute(code_ptr) };
+                f().to_le_bytes()
+            }
+            ValueKind::I64 | ValueKind::Str => {
+                let f: fn() -> i64 = unsafe { std::mem::transmute(code_ptr) };
+                f().to_le_bytes()
+            }
+        };
+
+        self.data_context.define(bytes.to_vec().into_boxed_slice());
+        let data_id = self
+            .module
+            .declare_data(
+                &format!("global_{name}_{}", self.repl_counter),
+                Linkage::Local,
+                false,
+                false,
+            )
+            .context("failed to declare global")?;
+        self.module
+            .define_data(data_id, &self.data_context)
+            .context("failed to define global")?;
+        self.data_context.clear();
+
+        self.globals.insert(name.to_string(), (data_id, kind));
+
+        Ok(())
+    }
+}
+
+impl Compiler<ObjectModule> {
+    /// Build a compiler that targets the host ISA and emits a relocatable object instead of
+    /// JIT-running the result. When `debug` is set, the emitted object also carries a
+    /// `.debug_line` section mapping code offsets back to `source_path`.
+    pub fn new_object(module_name: &str, debug: bool) -> Result<Self> {
+        let isa = host_isa(debug)?;
+        let builder = ObjectBuilder::new(
+            isa,
+            module_name.to_string(),
+            cranelift_module::default_libcall_names(),
+        )?;
+        let module = ObjectModule::new(builder);
+        let function_context = FunctionBuilderContext::new();
+        let codegen_context = module.make_context();
+        let data_context = DataContext::new();
+
+        Ok(Self {
+            module,
+            function_context,
+            codegen_context,
+            data_context,
+            variable_index: 0,
+            functions: HashMap::new(),
+            lambda_counter: 0,
+            globals: HashMap::new(),
+            repl_counter: 0,
+            debug,
+            debug_rows: HashMap::new(),
+        })
+    }
+
+    /// Compile `expr` (parsed from `source`) and write the resulting object file to
+    /// `output_path`, embedding a `.debug_line` section if `debug` was set at construction.
+    pub fn compile_to_object(mut self, expr: &Expr, source: &str, output_path: &str) -> Result<()> {
+        self.compile_named(expr, "main", source)?;
+
+        let debug_line = if self.debug {
+            Some(write_debug_line_section(source, &self.debug_rows)?)
+        } else {
+            None
+        };
+
+        let mut product = self.module.finish();
+        if let Some(debug_line) = debug_line {
+            let section_id = product.object.add_section(
+                Vec::new(),
+                b".debug_line".to_vec(),
+                object::SectionKind::Debug,
+            );
+            product.object.section_mut(section_id).set_data(debug_line, 1);
+        }
+
+        let bytes = product.emit().context("failed to emit object file")?;
+        std::fs::write(output_path, bytes).context("failed to write object file")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rnix::Root;
+
+    fn parse(source: &str) -> Expr {
+        let root = Root::parse(source);
+        assert!(root.errors().is_empty(), "parse errors: {:?}", root.errors());
+        root.tree().expr().expect("no expression")
+    }
+
+    fn compile_and_run_i64(source: &str) -> i64 {
+        let expr = parse(source);
+        let mut compiler = Compiler::<JITModule>::new(false).unwrap();
+        let (id, kind) = compiler.compile_named(&expr, "test", source).unwrap();
+        assert_eq!(kind, ValueKind::I64);
+        compiler.module.finalize_definitions().unwrap();
+        let code_ptr = compiler.module.get_finalized_function(id);
+        let main: fn() -> i64 = unsafe { std::mem::transmute(code_ptr) };
+        main()
+    }
+
+    fn compile_and_run_f64(source: &str) -> f64 {
+        let expr = parse(source);
+        let mut compiler = Compiler::<JITModule>::new(false).unwrap();
+        let (id, kind) = compiler.compile_named(&expr, "test", source).unwrap();
+        assert_eq!(kind, ValueKind::F64);
+        compiler.module.finalize_definitions().unwrap();
+        let code_ptr = compiler.module.get_finalized_function(id);
+        let main: fn() -> f64 = unsafe { std::mem::transmute(code_ptr) };
+        main()
+    }
+
+    #[test]
+    fn if_then_else_takes_the_true_branch() {
+        assert_eq!(compile_and_run_i64("if 1 < 2 then 10 else 20"), 10);
+    }
+
+    #[test]
+    fn if_then_else_takes_the_false_branch() {
+        assert_eq!(compile_and_run_i64("if 2 < 1 then 10 else 20"), 20);
+    }
+
+    #[test]
+    fn lambda_application_adds_argument() {
+        assert_eq!(compile_and_run_i64("let f = x: x + 1; in f 10"), 11);
+    }
+
+    #[test]
+    fn let_shadowing_uses_innermost_binding() {
+        assert_eq!(compile_and_run_i64("let x = 1; in let x = 2; in x"), 2);
+    }
+
+    #[test]
+    fn float_arithmetic_uses_float_instructions() {
+        assert_eq!(compile_and_run_f64("1.5 + 2.5"), 4.0);
+    }
+
+    #[test]
+    fn comparison_result_can_be_bound_as_a_value() {
+        assert_eq!(compile_and_run_i64("let c = 1 < 2; in c"), 1);
+    }
 }
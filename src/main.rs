@@ -1,9 +1,19 @@
+use std::io::Write;
+
 use anyhow::{Context, Result};
 use nix_llvm::Compiler;
 use rnix::Root;
 
 fn main() -> Result<()> {
-    let file_path = std::env::args().nth(1).context("no file path")?;
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+
+    let debug = take_flag(&mut args, "-g") || take_flag(&mut args, "--debug");
+
+    let mut args = args.into_iter();
+    let Some(file_path) = args.next() else {
+        return repl(debug);
+    };
+
     let file = std::fs::read_to_string(&file_path).context("failed to read file")?;
     let parse = Root::parse(&file);
 
@@ -18,8 +28,118 @@ fn main() -> Result<()> {
     let node = parse.tree().expr().context("no expression")?;
     println!("{:#?}", node);
 
-    let mut compiler = Compiler::new()?;
-    compiler.compile(&node)?;
+    match args.next().as_deref() {
+        Some("--emit=obj") => {
+            let output_path = args
+                .next()
+                .context("--emit=obj requires an output path")?;
+            let compiler = Compiler::new_object(&file_path, debug)?;
+            compiler.compile_to_object(&node, &file, &output_path)?;
+        }
+        Some(flag) => return Err(anyhow::anyhow!("unknown flag {}", flag)),
+        None => {
+            let mut compiler = Compiler::new(debug)?;
+            compiler.compile(&node, &file)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove the first occurrence of `flag` from `args`, returning whether it was present.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    match args.iter().position(|arg| arg == flag) {
+        Some(index) => {
+            args.remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Read Nix expressions from stdin and JIT-evaluate them one at a time, keeping the
+/// `Compiler`'s bindings alive across inputs so a `let`-binding entered on one line is
+/// visible on the next.
+fn repl(debug: bool) -> Result<()> {
+    let mut compiler = Compiler::new(debug)?;
+    let mut buffer = String::new();
+
+    loop {
+        print!("{}", if buffer.is_empty() { "nix-llvm> " } else { "......... " });
+        std::io::stdout().flush()?;
+
+        let mut line = String::new();
+        if std::io::stdin().read_line(&mut line)? == 0 {
+            break;
+        }
+
+        buffer.push_str(&line);
+
+        if is_incomplete(&buffer) {
+            continue;
+        }
+
+        let parse = Root::parse(&buffer);
+        if !parse.errors().is_empty() {
+            if looks_unfinished(&buffer, &parse.errors()) {
+                continue;
+            }
+
+            for error in parse.errors() {
+                eprintln!("error: {}", error);
+            }
+            buffer.clear();
+            continue;
+        }
+
+        let Some(expr) = parse.tree().expr() else {
+            buffer.clear();
+            continue;
+        };
+
+        if let Err(err) = compiler.eval(&expr, &buffer) {
+            eprintln!("error: {}", err);
+        }
+
+        buffer.clear();
+    }
 
     Ok(())
 }
+
+/// Whether `input` looks mid-expression and another line should be read before parsing,
+/// judged by whether its brackets are still unbalanced.
+fn is_incomplete(input: &str) -> bool {
+    let mut depth: i64 = 0;
+    for ch in input.chars() {
+        match ch {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+/// Whether every error in `errors` reflects the parser running out of input it was still
+/// expecting more of, rather than a genuine mistake in what's already been typed. Catches
+/// bracket-free continuations like `let x = 1;` on one line and `in x` on the next, where
+/// `is_incomplete`'s depth counter sees balanced (zero) nesting after the first line alone.
+fn looks_unfinished(input: &str, errors: &[rnix::parser::ParseError]) -> bool {
+    use rnix::parser::ParseError;
+
+    if errors.is_empty() {
+        return false;
+    }
+
+    let end = rowan::TextSize::of(input);
+    errors.iter().all(|error| match error {
+        ParseError::UnexpectedEOF | ParseError::UnexpectedEOFWanted(_) => true,
+        ParseError::Unexpected(range)
+        | ParseError::UnexpectedExtra(range)
+        | ParseError::UnexpectedDoubleBind(range)
+        | ParseError::UnexpectedWanted(_, range, _)
+        | ParseError::DuplicatedArgs(_, range) => range.end() >= end,
+        _ => false,
+    })
+}